@@ -1,39 +1,318 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::process::{Child, Command};
-use std::sync::Mutex;
+mod selection;
+mod single_instance;
+mod window_state;
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tauri::{
-    CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem,
-    Window,
+    CustomMenuItem, GlobalShortcutManager, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
+    SystemTrayMenuItem, Window,
 };
 
+/// Python 后端的运行状态，供托盘菜单和前端感知“后端是否就绪/已崩溃”。
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum BackendStatus {
+    /// 正在启动，尚未通过健康检查
+    Starting,
+    /// 健康检查通过，可以对外服务
+    Ready,
+    /// 连续重启多次仍失败，后端已放弃
+    Crashed,
+}
+
+/// 进程内保留的最近日志行数（环形缓冲上限）
+const LOG_RING_CAPACITY: usize = 1000;
+/// 单个日志文件的大小上限，超出后滚动为 `*.1`
+const LOG_ROTATE_BYTES: u64 = 1024 * 1024;
+
+/// 后端日志的内存环形缓冲（按行存放）；落盘由 [`tee_output`] 另行完成。
+type LogBuffer = Arc<Mutex<VecDeque<String>>>;
+
 /// 应用状态，管理Python服务进程
 struct AppState {
-    python_server: Mutex<Option<Child>>,
+    python_server: Arc<Mutex<Option<Child>>>,
+    backend_status: Arc<Mutex<BackendStatus>>,
+    /// 启动时分配的后端监听端口，前端与健康检查均以此为准
+    port: u16,
+    /// 后端 stdout/stderr 的内存环形缓冲，供前端快速拉取最近日志
+    logs: LogBuffer,
 }
 
-/// 启动Python FastAPI服务
-fn start_python_server() -> Result<Child, std::io::Error> {
+/// 监控线程在放弃前尝试重启后端的最大次数
+const MAX_RESTARTS: u32 = 5;
+/// 两次重启之间的冷却时间
+const RESTART_COOLDOWN: Duration = Duration::from_secs(3);
+/// 健康检查的总超时时间
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+/// 监控线程对存活进程做健康探测的间隔
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+/// 进程存活但持续不健康多久后判定为“挂起”并强制重启
+const UNHEALTHY_GRACE: Duration = Duration::from_secs(30);
+
+/// 向操作系统申请一个空闲端口：绑定 `127.0.0.1:0`，读取分配到的端口后立即释放，
+/// 由 uvicorn 重新占用。若申请失败则回退到历史默认端口 8000。
+fn pick_free_port() -> u16 {
+    match TcpListener::bind("127.0.0.1:0").and_then(|l| l.local_addr()) {
+        Ok(addr) => addr.port(),
+        Err(e) => {
+            eprintln!("申请空闲端口失败，回退到 8000: {}", e);
+            8000
+        }
+    }
+}
+
+/// 查找打包随发行版的后端可执行文件（PyInstaller/内嵌解释器）。
+/// Tauri 会将 sidecar 放在主程序同目录，存在时优先使用，使终端用户无需预装 Python。
+fn sidecar_path() -> Option<PathBuf> {
+    let dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+
+    #[cfg(target_os = "windows")]
+    let name = "promethea-backend.exe";
+    #[cfg(not(target_os = "windows"))]
+    let name = "promethea-backend";
+
+    let candidate = dir.join(name);
+    candidate.exists().then_some(candidate)
+}
+
+/// 构造后端启动命令：存在打包好的 sidecar 时直接运行它，
+/// 否则回退到系统 `python`/`python3`（仅用于开发环境）。
+fn backend_command(port: u16) -> Command {
+    let port = port.to_string();
+
+    if let Some(sidecar) = sidecar_path() {
+        println!("使用打包的后端可执行文件: {}", sidecar.display());
+        let mut cmd = Command::new(sidecar);
+        cmd.args(&["--host", "127.0.0.1", "--port", &port]);
+        return cmd;
+    }
+
     #[cfg(target_os = "windows")]
     let python_cmd = "python";
-    
     #[cfg(not(target_os = "windows"))]
     let python_cmd = "python3";
 
-    println!("正在启动 Python 服务...");
-    
-    let child = Command::new(python_cmd)
-        .args(&["-m", "uvicorn", "api_server.server:app", "--host", "127.0.0.1", "--port", "8000"])
+    let mut cmd = Command::new(python_cmd);
+    cmd.args(&["-m", "uvicorn", "api_server.server:app", "--host", "127.0.0.1", "--port", &port]);
+    cmd
+}
+
+/// 后端日志文件路径：`<数据目录>/Promethea/promethea-backend.log`。
+fn log_file_path() -> PathBuf {
+    let base = tauri::api::path::data_dir().unwrap_or_else(std::env::temp_dir);
+    base.join("Promethea").join("promethea-backend.log")
+}
+
+/// 向日志文件追加一行，文件超过 [`LOG_ROTATE_BYTES`] 时先滚动为 `*.1`。
+fn append_log_line(path: &PathBuf, line: &str) {
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if std::fs::metadata(path).map(|m| m.len()).unwrap_or(0) >= LOG_ROTATE_BYTES {
+        let _ = std::fs::rename(path, path.with_extension("log.1"));
+    }
+    if let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open(path) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// 逐行读取子进程的某个输出流，将每行同时回显、落盘并写入内存环形缓冲。
+fn tee_output<R: Read + Send + 'static>(reader: R, label: &'static str, logs: LogBuffer) {
+    std::thread::spawn(move || {
+        let path = log_file_path();
+        for line in BufReader::new(reader).lines().map_while(Result::ok) {
+            let entry = format!("[{}] {}", label, line);
+            println!("{}", entry);
+            append_log_line(&path, &entry);
+
+            let mut ring = logs.lock().unwrap();
+            if ring.len() >= LOG_RING_CAPACITY {
+                ring.pop_front();
+            }
+            ring.push_back(entry);
+        }
+    });
+}
+
+/// 启动Python FastAPI服务
+fn start_python_server(port: u16, logs: &LogBuffer) -> Result<Child, std::io::Error> {
+    println!("正在启动 Python 服务（端口 {}）...", port);
+
+    let mut child = backend_command(port)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()?;
-    
+
     println!("Python 服务已启动，PID: {}", child.id());
-    
-    // 等待服务启动
-    std::thread::sleep(std::time::Duration::from_secs(2));
-    
+
+    // 接管子进程输出，避免在 windows_subsystem = "windows" 的发行版里丢失日志。
+    if let Some(stdout) = child.stdout.take() {
+        tee_output(stdout, "stdout", Arc::clone(logs));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        tee_output(stderr, "stderr", Arc::clone(logs));
+    }
+
     Ok(child)
 }
 
+/// 轮询 `GET /health` 直到返回 200，采用指数退避（100ms、200ms、400ms…最多约2s），
+/// 总超时 [`READY_TIMEOUT`]。就绪返回 `true`，超时返回 `false`。
+fn wait_for_ready(port: u16) -> bool {
+    let deadline = std::time::Instant::now() + READY_TIMEOUT;
+    let mut backoff = Duration::from_millis(100);
+    let health_url = format!("http://127.0.0.1:{}/health", port);
+    let root_url = format!("http://127.0.0.1:{}/", port);
+
+    while std::time::Instant::now() < deadline {
+        // 优先探测 /health，回退到根路径，兼容尚未提供健康端点的后端。
+        if http_ok(&health_url) || http_ok(&root_url) {
+            println!("Python 服务健康检查通过");
+            return true;
+        }
+        std::thread::sleep(backoff);
+        backoff = std::cmp::min(backoff * 2, Duration::from_secs(2));
+    }
+
+    eprintln!("Python 服务在 {:?} 内未通过健康检查", READY_TIMEOUT);
+    false
+}
+
+/// 对后端做一次健康探测：优先 `/health`，回退到根路径。
+fn backend_healthy(port: u16) -> bool {
+    http_ok(&format!("http://127.0.0.1:{}/health", port))
+        || http_ok(&format!("http://127.0.0.1:{}/", port))
+}
+
+/// 对给定 URL 发起 GET 请求，返回是否得到 2xx 响应。
+fn http_ok(url: &str) -> bool {
+    match reqwest::blocking::Client::new()
+        .get(url)
+        .timeout(Duration::from_secs(2))
+        .send()
+    {
+        Ok(resp) => resp.status().is_success(),
+        Err(_) => false,
+    }
+}
+
+/// 判断连续失败次数是否已超出重启预算，达到即应标记为 [`BackendStatus::Crashed`]。
+fn exceeded_restart_budget(failures: u32, max_restarts: u32) -> bool {
+    failures > max_restarts
+}
+
+/// 监控后端子进程：退出时按冷却时间重启；进程存活但持续不健康（挂起）
+/// 超过 [`UNHEALTHY_GRACE`] 时强制重启。连续失败超过 [`MAX_RESTARTS`] 次后
+/// 将状态标记为 [`BackendStatus::Crashed`]。
+fn spawn_supervisor(
+    server: Arc<Mutex<Option<Child>>>,
+    status: Arc<Mutex<BackendStatus>>,
+    port: u16,
+    logs: LogBuffer,
+) {
+    const TICK: Duration = Duration::from_secs(1);
+
+    std::thread::spawn(move || {
+        let mut failures: u32 = 0;
+        let mut since_probe = Duration::ZERO;
+        let mut unhealthy_for = Duration::ZERO;
+
+        loop {
+            std::thread::sleep(TICK);
+
+            // 轮询子进程是否已退出；try_wait 不会长时间占用锁。
+            let exited = {
+                let mut guard = server.lock().unwrap();
+                match guard.as_mut() {
+                    Some(child) => child.try_wait().ok().flatten(),
+                    None => None,
+                }
+            };
+
+            if let Some(exit_status) = exited {
+                eprintln!("Python 服务意外退出：{}", exit_status);
+                since_probe = Duration::ZERO;
+                unhealthy_for = Duration::ZERO;
+
+                // 重启循环：每次尝试（无论子进程退出还是 spawn 失败）都计入失败次数，
+                // 超过 [`MAX_RESTARTS`] 即标记为 Crashed，绝不让失败的 spawn 把守护卡死。
+                let mut gave_up = false;
+                loop {
+                    failures += 1;
+                    if exceeded_restart_budget(failures, MAX_RESTARTS) {
+                        eprintln!("Python 服务连续重启 {} 次仍失败，已放弃", MAX_RESTARTS);
+                        *status.lock().unwrap() = BackendStatus::Crashed;
+                        *server.lock().unwrap() = None;
+                        gave_up = true;
+                        break;
+                    }
+
+                    *status.lock().unwrap() = BackendStatus::Starting;
+                    std::thread::sleep(RESTART_COOLDOWN);
+                    println!("正在重启 Python 服务（第 {} 次）...", failures);
+
+                    match start_python_server(port, &logs) {
+                        Ok(child) => {
+                            *server.lock().unwrap() = Some(child);
+                            if wait_for_ready(port) {
+                                *status.lock().unwrap() = BackendStatus::Ready;
+                                // 成功恢复后清零失败计数，偶发崩溃不累积。
+                                failures = 0;
+                            }
+                            // 新进程已拉起（即便尚未就绪），交回外层循环由健康检查兜底。
+                            break;
+                        }
+                        Err(e) => {
+                            // spawn 失败不能直接退出，继续重试直至就绪或达到上限。
+                            eprintln!("重启 Python 服务失败: {}", e);
+                            *server.lock().unwrap() = None;
+                        }
+                    }
+                }
+
+                if gave_up {
+                    break;
+                }
+                continue;
+            }
+
+            // 进程仍存活：定期做健康探测，捕捉“进程未退出但一直不健康”的挂起。
+            since_probe += TICK;
+            if since_probe < HEALTH_CHECK_INTERVAL {
+                continue;
+            }
+            since_probe = Duration::ZERO;
+
+            if backend_healthy(port) {
+                unhealthy_for = Duration::ZERO;
+                let mut guard = status.lock().unwrap();
+                if *guard != BackendStatus::Crashed {
+                    *guard = BackendStatus::Ready;
+                }
+            } else {
+                unhealthy_for += HEALTH_CHECK_INTERVAL;
+                eprintln!("后端存活但健康检查未通过，已持续 {:?}", unhealthy_for);
+                if unhealthy_for >= UNHEALTHY_GRACE {
+                    eprintln!("后端疑似挂起，强制重启");
+                    unhealthy_for = Duration::ZERO;
+                    *status.lock().unwrap() = BackendStatus::Starting;
+                    // 杀掉挂起进程，由下一轮 try_wait 触发重启流程。
+                    if let Some(child) = server.lock().unwrap().as_mut() {
+                        let _ = child.kill();
+                    }
+                }
+            }
+        }
+    });
+}
+
 /// 停止Python服务
 fn stop_python_server(child: &mut Child) {
     println!("正在停止 Python 服务...");
@@ -45,14 +324,22 @@ fn stop_python_server(child: &mut Child) {
 fn create_system_tray() -> SystemTray {
     let open = CustomMenuItem::new("open".to_string(), "打开主窗口");
     let hide = CustomMenuItem::new("hide".to_string(), "隐藏窗口");
+    let logs = CustomMenuItem::new("logs".to_string(), "查看日志");
+    // 勾选状态反映已持久化的“启动时隐藏到托盘”偏好。
+    let mut start_hidden = CustomMenuItem::new("start_hidden".to_string(), "启动时隐藏到托盘");
+    if window_state::start_hidden() {
+        start_hidden = start_hidden.selected();
+    }
     let quit = CustomMenuItem::new("quit".to_string(), "退出 Promethea");
-    
+
     let tray_menu = SystemTrayMenu::new()
         .add_item(open)
         .add_item(hide)
+        .add_item(logs)
+        .add_item(start_hidden)
         .add_native_item(SystemTrayMenuItem::Separator)
         .add_item(quit);
-    
+
     SystemTray::new().with_menu(tray_menu)
 }
 
@@ -69,6 +356,27 @@ fn handle_system_tray_event(app: &tauri::AppHandle, event: SystemTrayEvent) {
                 "hide" => {
                     window.hide().unwrap();
                 }
+                "logs" => {
+                    // 在系统文件管理器/默认程序中打开日志文件，便于排查后端启动失败。
+                    let path = log_file_path();
+                    if let Err(e) =
+                        tauri::api::shell::open(&app.shell_scope(), path.to_string_lossy(), None)
+                    {
+                        eprintln!("打开日志文件失败: {}", e);
+                    }
+                }
+                "start_hidden" => {
+                    // 切换“启动时隐藏到托盘”偏好并同步托盘勾选状态。
+                    let value = !window_state::start_hidden();
+                    window_state::set_start_hidden(&window, value);
+                    if let Err(e) = app
+                        .tray_handle()
+                        .get_item("start_hidden")
+                        .set_selected(value)
+                    {
+                        eprintln!("更新托盘勾选状态失败: {}", e);
+                    }
+                }
                 "quit" => {
                     // 清理Python服务
                     if let Some(state) = app.try_state::<AppState>() {
@@ -98,9 +406,105 @@ fn handle_window_close_event(window: &Window) {
     window.hide().unwrap();
 }
 
+/// 返回当前后端运行状态，供前端轮询展示“后端已崩溃”等提示。
+#[tauri::command]
+fn backend_status(state: tauri::State<AppState>) -> String {
+    let status = *state.backend_status.lock().unwrap();
+    format!("{:?}", status)
+}
+
+/// 返回后端实际监听的端口，前端据此拼接 API 地址，避免硬编码 8000。
+#[tauri::command]
+fn get_backend_port(state: tauri::State<AppState>) -> u16 {
+    state.port
+}
+
+/// 返回内存环形缓冲中最近的后端日志行，供前端日志面板展示。
+#[tauri::command]
+fn get_recent_logs(state: tauri::State<AppState>) -> Vec<String> {
+    state.logs.lock().unwrap().iter().cloned().collect()
+}
+
+/// 读取前台应用当前选中的文本，暴露给前端按需调用。
+#[tauri::command]
+fn get_selection_text(app: tauri::AppHandle) -> Result<String, String> {
+    selection::get_selection_text(&app)
+}
+
+/// 将一段文本作为新的 agent 提示 POST 给正在运行的后端，返回后端回复。
+fn post_prompt(port: u16, prompt: &str) -> Result<String, String> {
+    let url = format!("http://127.0.0.1:{}/api/prompt", port);
+    reqwest::blocking::Client::new()
+        .post(url)
+        .json(&serde_json::json!({ "prompt": prompt }))
+        .timeout(Duration::from_secs(60))
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .map_err(|e| e.to_string())
+}
+
+/// 显隐主窗口：已显示则隐藏到托盘，否则弹出并聚焦。
+fn toggle_main_window(app: &tauri::AppHandle) {
+    if let Some(window) = app.get_window("main") {
+        if window.is_visible().unwrap_or(false) {
+            let _ = window.hide();
+        } else {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    }
+}
+
+/// 注册全局快捷键：`CmdOrCtrl+Shift+Space` 切换窗口，
+/// `CmdOrCtrl+Shift+A` 抓取选中文本发给 agent 并弹出窗口展示回复。
+fn register_global_shortcuts(app: &tauri::AppHandle, port: u16) {
+    let mut manager = app.global_shortcut_manager();
+
+    let toggle_app = app.clone();
+    if let Err(e) = manager.register("CmdOrCtrl+Shift+Space", move || {
+        toggle_main_window(&toggle_app);
+    }) {
+        eprintln!("注册窗口切换快捷键失败: {}", e);
+    }
+
+    let ask_app = app.clone();
+    if let Err(e) = manager.register("CmdOrCtrl+Shift+A", move || {
+        match selection::get_selection_text(&ask_app) {
+            Ok(text) => match post_prompt(port, &text) {
+                Ok(reply) => {
+                    if let Some(window) = ask_app.get_window("main") {
+                        let _ = window.show();
+                        let _ = window.set_focus();
+                        // 把回复交给前端展示。
+                        let _ = window.emit("agent-response", reply);
+                    }
+                }
+                Err(e) => eprintln!("发送选中文本到 agent 失败: {}", e),
+            },
+            Err(e) => eprintln!("获取选中文本失败: {}", e),
+        }
+    }) {
+        eprintln!("注册划词提问快捷键失败: {}", e);
+    }
+}
+
 fn main() {
+    // 单实例保护：若已有实例在托盘运行，通知其聚焦并退出，
+    // 绝不再启动第二个 Python 后端。必须在拉起后端之前完成。
+    let focus_rx = match single_instance::acquire() {
+        single_instance::Acquired::Primary(rx) => rx,
+        single_instance::Acquired::Secondary => std::process::exit(0),
+    };
+
+    // 动态分配一个空闲端口，避免 8000 被占用时无法启动。
+    let port = pick_free_port();
+
+    // 后端日志的内存环形缓冲，reader 线程与前端命令共享。
+    let logs: LogBuffer = Arc::new(Mutex::new(VecDeque::with_capacity(LOG_RING_CAPACITY)));
+
     // 启动Python服务
-    let python_server = match start_python_server() {
+    let python_server = match start_python_server(port, &logs) {
         Ok(child) => Some(child),
         Err(e) => {
             eprintln!("启动 Python 服务失败: {}", e);
@@ -112,10 +516,28 @@ fn main() {
         }
     };
 
+    // 等待后端通过健康检查，再据此初始化状态。
+    let initial_status = if wait_for_ready(port) {
+        BackendStatus::Ready
+    } else {
+        BackendStatus::Starting
+    };
+
     let app_state = AppState {
-        python_server: Mutex::new(python_server),
+        python_server: Arc::new(Mutex::new(python_server)),
+        backend_status: Arc::new(Mutex::new(initial_status)),
+        port,
+        logs: Arc::clone(&logs),
     };
 
+    // 启动监控线程，后端异常退出时自动重启。
+    spawn_supervisor(
+        Arc::clone(&app_state.python_server),
+        Arc::clone(&app_state.backend_status),
+        port,
+        Arc::clone(&logs),
+    );
+
     // 创建系统托盘
     let tray = create_system_tray();
 
@@ -124,19 +546,69 @@ fn main() {
         .manage(app_state)
         .system_tray(tray)
         .on_system_tray_event(handle_system_tray_event)
+        .invoke_handler(tauri::generate_handler![
+            backend_status,
+            get_backend_port,
+            get_recent_logs,
+            get_selection_text
+        ])
         .on_window_event(|event| {
             if let tauri::WindowEvent::CloseRequested { api, .. } = event.event() {
+                // 仅在关闭时落盘窗口几何信息，避免拖动/缩放时频繁读写磁盘。
+                window_state::persist(event.window());
                 event.window().hide().unwrap();
                 api.prevent_close();
             }
         })
-        .setup(|app| {
+        .setup(move |app| {
             // 应用启动时的初始化
             println!("Promethea Agent 已启动");
-            println!("Web界面地址: http://127.0.0.1:8000");
+            println!("Web界面地址: http://127.0.0.1:{}", port);
+            // 将动态端口注入已加载的窗口，使前端访问正确的后端地址。
+            if let Some(window) = app.get_window("main") {
+                let _ = window.eval(&format!(
+                    "window.location.replace('http://127.0.0.1:{}/')",
+                    port
+                ));
+
+                // 恢复上次的窗口几何信息；若用户偏好启动时隐藏则留在托盘。
+                let start_hidden = window_state::restore(&window);
+                if start_hidden {
+                    let _ = window.hide();
+                } else {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+            }
+
+            // 注册全局快捷键：一个切换窗口显隐，一个把选中文本发给 agent。
+            register_global_shortcuts(&app.handle(), port);
+
+            // 消费聚焦事件：后续实例的“聚焦”请求会把本窗口带到前台。
+            // accept/握手早在 acquire() 里就已开始，这里仅接管事件回调。
+            let focus_app = app.handle();
+            single_instance::on_focus_requested(focus_rx, move || {
+                if let Some(window) = focus_app.get_window("main") {
+                    let _ = window.show();
+                    let _ = window.set_focus();
+                }
+            });
             Ok(())
         })
         .run(tauri::generate_context!())
         .expect("启动 Tauri 应用失败");
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restart_budget_triggers_crashed_after_max() {
+        // 未超预算时应继续重试。
+        assert!(!exceeded_restart_budget(1, MAX_RESTARTS));
+        assert!(!exceeded_restart_budget(MAX_RESTARTS, MAX_RESTARTS));
+        // 第 MAX_RESTARTS + 1 次失败触发放弃（Crashed）。
+        assert!(exceeded_restart_budget(MAX_RESTARTS + 1, MAX_RESTARTS));
+    }
+}
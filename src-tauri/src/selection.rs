@@ -0,0 +1,83 @@
+//! 抓取前台应用当前选中的文本：模拟“复制”快捷键后读取剪贴板。
+//! 各平台仅复制修饰键不同，其余逻辑共用。
+
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, ClipboardManager};
+
+/// 轮询剪贴板变化的总超时时间，兼顾慢速应用。
+const COPY_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// 模拟一次“复制”按键：macOS 用 Cmd+C，其余平台用 Ctrl+C。
+///
+/// 触发提问的热键是 `CmdOrCtrl+Shift+A`，回调执行时用户往往仍按着 Shift，
+/// 若不先松开，合成出来的会是 Ctrl+Shift+C——多数应用并不把它当作复制，
+/// 剪贴板也就不会更新。因此先抬起可能残留的修饰键再合成复制键。
+fn simulate_copy() -> Result<(), String> {
+    use enigo::{Enigo, Key, KeyboardControllable};
+
+    let mut enigo = Enigo::new();
+
+    // 抬起仍被物理按住的修饰键，确保只剩纯粹的复制组合键。
+    enigo.key_up(Key::Shift);
+    enigo.key_up(Key::Alt);
+    enigo.key_up(Key::Control);
+    enigo.key_up(Key::Meta);
+
+    #[cfg(target_os = "macos")]
+    let modifier = Key::Meta;
+    #[cfg(not(target_os = "macos"))]
+    let modifier = Key::Control;
+
+    enigo.key_down(modifier);
+    enigo.key_click(Key::Layout('c'));
+    enigo.key_up(modifier);
+    Ok(())
+}
+
+/// 读取前台应用当前选中的文本：先记录旧剪贴板内容触发复制，
+/// 再轮询等待剪贴板发生变化。若选中内容与剪贴板原有内容恰好相同（例如连续两次
+/// 询问同一段文字），值不会“变化”，此时只要复制后剪贴板非空即视为捕获成功，
+/// 避免误报“未捕获到选中文本”。
+pub fn get_selection_text(app: &AppHandle) -> Result<String, String> {
+    let clipboard = app.clipboard_manager();
+    let previous = clipboard.read_text().map_err(|e| e.to_string())?;
+
+    simulate_copy()?;
+
+    // 轮询剪贴板直到内容相对复制前发生变化或超时，避免对慢速应用写死固定等待。
+    let deadline = Instant::now() + COPY_TIMEOUT;
+    loop {
+        std::thread::sleep(Duration::from_millis(40));
+        let current = clipboard.read_text().map_err(|e| e.to_string())?;
+        if current != previous {
+            return non_empty_or_err(current);
+        }
+        if Instant::now() >= deadline {
+            // 超时仍未变化：选区可能与原剪贴板相同，非空即按成功处理。
+            return non_empty_or_err(current);
+        }
+    }
+}
+
+/// 将剪贴板内容规整为结果：非空返回文本，空或纯空白视为未捕获。
+fn non_empty_or_err(text: Option<String>) -> Result<String, String> {
+    let text = text.unwrap_or_default();
+    if text.trim().is_empty() {
+        return Err("未捕获到选中文本".to_string());
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_empty_clipboard_counts_as_captured() {
+        // 非空（即便与旧值相同）视为成功。
+        assert_eq!(non_empty_or_err(Some("hello".into())), Ok("hello".into()));
+        // 空或纯空白、缺失均视为未捕获。
+        assert!(non_empty_or_err(Some("   ".into())).is_err());
+        assert!(non_empty_or_err(None).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! 单实例保护：主实例在回环接口上申请一个 OS 分配的控制端口，并把端口号写入
+//! 配置目录下的锁文件 `Promethea/instance.port`；后续实例读取该端口、用约定口令握手确认对端确实是
+//! Promethea 后再请求“聚焦”，避免把陌生进程误判为已运行的主实例。
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+/// 握手口令，用于区分“端口被无关进程占用”和“我们自己的实例在监听”。
+const MAGIC: &str = "PROMETHEA_FOCUS_v1";
+
+/// 接受连接后读取握手口令的超时时间，防止半开连接长期阻塞 accept 循环。
+const ACCEPT_READ_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// `acquire` 的结果：本进程是主实例（持有聚焦事件通道），还是应退出的后续实例。
+pub enum Acquired {
+    Primary(Receiver<()>),
+    Secondary,
+}
+
+/// 锁文件路径：`<配置目录>/Promethea/instance.port`，记录主实例的控制端口。
+fn lock_path() -> PathBuf {
+    let base = tauri::api::path::config_dir().unwrap_or_else(std::env::temp_dir);
+    base.join("Promethea").join("instance.port")
+}
+
+fn read_port() -> Option<u16> {
+    std::fs::read_to_string(lock_path()).ok()?.trim().parse().ok()
+}
+
+fn write_port(port: u16) {
+    let path = lock_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let _ = std::fs::write(path, port.to_string());
+}
+
+/// 向疑似主实例握手并请求聚焦：仅当对端回送约定口令时才确认成功。
+/// 连接失败或口令不符（端口空闲 / 被陌生进程占用 / 锁文件残留）均返回 `false`。
+fn signal_focus(port: u16) -> bool {
+    let Ok(stream) = TcpStream::connect(("127.0.0.1", port)) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    let mut stream = stream;
+
+    if stream.write_all(format!("{}\n", MAGIC).as_bytes()).is_err() {
+        return false;
+    }
+
+    let mut reply = String::new();
+    if BufReader::new(&mut stream).read_line(&mut reply).is_err() {
+        return false;
+    }
+    reply.trim() == MAGIC
+}
+
+/// 处理单个控制连接：校验握手口令、回送口令，成功则返回 `true`（需要聚焦）。
+/// 为每个连接设置读超时，避免半开连接卡死单线程 accept 循环。
+fn handle_connection(stream: TcpStream) -> bool {
+    let _ = stream.set_read_timeout(Some(ACCEPT_READ_TIMEOUT));
+    let mut stream = stream;
+    let mut line = String::new();
+    if BufReader::new(&mut stream).read_line(&mut line).is_err() {
+        return false;
+    }
+    if line.trim() == MAGIC {
+        let _ = stream.write_all(format!("{}\n", MAGIC).as_bytes());
+        return true;
+    }
+    false
+}
+
+/// 立即启动 accept 线程：自绑定端口起就能响应握手，使守护在漫长的启动过程
+/// （`wait_for_ready` 可达 30s）中始终有效。每收到一次有效聚焦请求就向通道发送事件。
+fn spawn_accept_loop(listener: TcpListener, tx: Sender<()>) {
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            if handle_connection(stream) {
+                let _ = tx.send(());
+            }
+        }
+    });
+}
+
+/// 尝试成为主实例：若锁文件指向的端口上确有 Promethea 主实例，则请求其聚焦并退出；
+/// 否则申请一个新的控制端口、写入锁文件，并立即开始监听握手，返回聚焦事件通道。
+pub fn acquire() -> Acquired {
+    if let Some(port) = read_port() {
+        if signal_focus(port) {
+            return Acquired::Secondary;
+        }
+    }
+
+    // 绑定端口 0，由操作系统分配一个空闲端口，避免任何硬编码端口。
+    match TcpListener::bind("127.0.0.1:0") {
+        Ok(listener) => {
+            if let Ok(addr) = listener.local_addr() {
+                write_port(addr.port());
+            }
+            let (tx, rx) = mpsc::channel();
+            spawn_accept_loop(listener, tx);
+            Acquired::Primary(rx)
+        }
+        Err(e) => {
+            // 回环上几乎不可能失败；保守起见仍以主实例身份启动。
+            eprintln!("绑定单实例控制端口失败: {}", e);
+            let (_tx, rx) = mpsc::channel();
+            Acquired::Primary(rx)
+        }
+    }
+}
+
+/// 消费聚焦事件通道：每当有后续实例请求聚焦时执行回调（通常是 show/set_focus）。
+/// 在启动过程中到达的事件会缓存在通道里，待回调注册后立即投递。
+pub fn on_focus_requested<F: Fn() + Send + 'static>(rx: Receiver<()>, on_focus: F) {
+    std::thread::spawn(move || {
+        for _ in rx {
+            on_focus();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 绑定一个临时监听器并启动 accept 线程，模拟后续实例握手，
+    /// 验证有效口令会触发聚焦事件、错误口令不会。
+    #[test]
+    fn handshake_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::channel();
+        spawn_accept_loop(listener, tx);
+
+        // 正确口令：signal_focus 成功并触发一次事件。
+        assert!(signal_focus(port));
+        assert!(rx.recv_timeout(Duration::from_secs(2)).is_ok());
+
+        // 错误口令：不回送 MAGIC，不产生聚焦事件。
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(b"not-the-magic\n").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
+}
@@ -0,0 +1,115 @@
+//! 窗口状态的持久化：记住用户摆放的位置、大小、最大化状态，
+//! 以及“启动时隐藏到托盘”的偏好，下次启动时原样恢复。
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{PhysicalPosition, PhysicalSize, Window};
+
+/// 序列化到磁盘的窗口状态。
+#[derive(Serialize, Deserialize, Clone)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    /// 启动时隐藏到托盘，不弹出主窗口
+    pub start_hidden: bool,
+}
+
+/// 状态文件路径：`<配置目录>/Promethea/window-state.json`。
+fn state_path() -> PathBuf {
+    let base = tauri::api::path::config_dir().unwrap_or_else(std::env::temp_dir);
+    base.join("Promethea").join("window-state.json")
+}
+
+/// 读取已保存的窗口状态，文件缺失或损坏时返回 `None`。
+pub fn load() -> Option<WindowState> {
+    let data = std::fs::read_to_string(state_path()).ok()?;
+    serde_json::from_str(&data).ok()
+}
+
+/// 将窗口状态写入磁盘，目录不存在时自动创建。
+fn save(state: &WindowState) {
+    let path = state_path();
+    if let Some(dir) = path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(state) {
+        let _ = std::fs::write(path, json);
+    }
+}
+
+/// 采集窗口当前几何信息，并以给定的 `start_hidden` 偏好组装状态。
+fn capture(window: &Window, start_hidden: bool) -> WindowState {
+    let position = window.outer_position().unwrap_or(PhysicalPosition::new(0, 0));
+    let size = window.inner_size().unwrap_or(PhysicalSize::new(1024, 768));
+
+    WindowState {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+        maximized: window.is_maximized().unwrap_or(false),
+        start_hidden,
+    }
+}
+
+/// 采集窗口当前的几何信息并落盘，保留既有的 `start_hidden` 偏好。
+///
+/// 只在点击关闭按钮（`CloseRequested`）时落盘，拖动/缩放过程中不读写磁盘。
+/// 几何信息在落盘时从窗口实时采集。托盘“退出”菜单直接结束进程而不落盘，
+/// 因此上次关闭窗口之后的移动/缩放会在退出时丢失；进程被强杀/崩溃时同样如此。
+pub fn persist(window: &Window) {
+    save(&capture(window, start_hidden()));
+}
+
+/// 读取当前的“启动时隐藏到托盘”偏好。
+pub fn start_hidden() -> bool {
+    load().map(|s| s.start_hidden).unwrap_or(false)
+}
+
+/// 设置“启动时隐藏到托盘”偏好，同时保留窗口当前几何信息。
+pub fn set_start_hidden(window: &Window, value: bool) {
+    save(&capture(window, value));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn window_state_json_round_trip() {
+        let state = WindowState {
+            x: -30,
+            y: 120,
+            width: 1280,
+            height: 720,
+            maximized: true,
+            start_hidden: true,
+        };
+
+        let json = serde_json::to_string(&state).unwrap();
+        let restored: WindowState = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.x, state.x);
+        assert_eq!(restored.y, state.y);
+        assert_eq!(restored.width, state.width);
+        assert_eq!(restored.height, state.height);
+        assert_eq!(restored.maximized, state.maximized);
+        assert_eq!(restored.start_hidden, state.start_hidden);
+    }
+}
+
+/// 在窗口显示前恢复几何信息，返回是否应以“隐藏到托盘”方式启动。
+pub fn restore(window: &Window) -> bool {
+    let Some(state) = load() else { return false };
+
+    let _ = window.set_position(PhysicalPosition::new(state.x, state.y));
+    let _ = window.set_size(PhysicalSize::new(state.width, state.height));
+    if state.maximized {
+        let _ = window.maximize();
+    }
+
+    state.start_hidden
+}